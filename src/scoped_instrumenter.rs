@@ -1,10 +1,12 @@
 use std::{
     collections::HashMap,
-    env,
     fs::{self, File, OpenOptions},
     io::{ErrorKind, Write},
     path::PathBuf,
-    sync::Mutex,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
     time::{Duration, Instant},
 };
 
@@ -12,11 +14,37 @@ use lazy_static::lazy_static;
 
 use crate::isa::Instruction;
 
+mod cost_model;
+mod cost_table;
+mod filter;
+mod fuel;
+
+pub use cost_model::{fit_cost_table, fit_metrics, FittedCost};
+pub use cost_table::{CostTable, InstructionCost};
+pub use filter::{set_instruction_filter, InstructionFilter, InstructionGroup};
+pub use fuel::{FuelError, FuelMeter};
+
 const TARGET_ENTRY_COUNT: usize = 10_000;
 
+/// A handler invoked once for every instrumented instruction as it finishes executing.
+///
+/// The handler receives the instruction's name, the feature vector captured for it (see
+/// [`ScopedInstrumenter::new`]), and the wall-clock [`Duration`] the instruction took. Returning
+/// `false` records a halt request that [`halt::requested`] reports; nothing in the interpreter
+/// polls that request, so execution continues either way.
+///
+/// The handler must not itself drive instrumented Wasm execution: the global handler mutex is held
+/// across the call, so re-entering instrumentation from inside a handler would deadlock.
+pub type TraceHandler = Box<dyn FnMut(&'static str, &[String], Duration) -> bool + Send>;
+
+/// Fast-path flag mirroring whether [`TRACE_HANDLER`] currently holds a handler.
+///
+/// [`ScopedInstrumenter`]'s `Drop` checks this with an acquire load before touching the mutex, so an
+/// un-instrumented run pays a single atomic load per instruction instead of a lock acquisition.
+static HANDLER_INSTALLED: AtomicBool = AtomicBool::new(false);
+
 lazy_static! {
-    static ref INSTRUMENTATION_FILES: Mutex<HashMap<&'static str, InstrumentationFile>> =
-        Mutex::new(HashMap::new());
+    static ref TRACE_HANDLER: Mutex<Option<TraceHandler>> = Mutex::new(None);
     static ref OUTPUT_DIR: PathBuf = {
         let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
             .join("target")
@@ -27,6 +55,49 @@ lazy_static! {
     };
 }
 
+/// Registers `handler` as the global trace handler, replacing any previous one.
+///
+/// With no handler registered instrumentation is inert: [`ScopedInstrumenter`] still times each
+/// instruction but the measurement is discarded, so embedders pay no file I/O unless they opt in.
+pub fn set_trace_handler(handler: TraceHandler) {
+    *TRACE_HANDLER.lock().unwrap() = Some(handler);
+    HANDLER_INSTALLED.store(true, Ordering::Release);
+}
+
+/// Removes the global trace handler, returning instrumentation to its inert state.
+pub fn clear_trace_handler() {
+    HANDLER_INSTALLED.store(false, Ordering::Release);
+    *TRACE_HANDLER.lock().unwrap() = None;
+}
+
+/// Installs the built-in CSV sink as the global trace handler.
+///
+/// This reproduces the historical behaviour of writing up to [`TARGET_ENTRY_COUNT`] rows of
+/// `(args, n_exec, total_elapsed_time)` per instruction into `target/metrics/<name>.csv`. It is
+/// now just one implementation of [`TraceHandler`] rather than a cost forced on every embedder.
+pub fn use_csv_trace_handler() {
+    let mut sink = CsvSink::default();
+    set_trace_handler(Box::new(move |instruction, properties, duration| {
+        sink.record(instruction, properties, duration);
+        true
+    }));
+}
+
+/// Built-in [`TraceHandler`] that appends each measurement to a per-instruction CSV file.
+#[derive(Debug, Default)]
+struct CsvSink {
+    files: HashMap<&'static str, InstrumentationFile>,
+}
+
+impl CsvSink {
+    fn record(&mut self, instruction: &'static str, properties: &[String], duration: Duration) {
+        self.files
+            .entry(instruction)
+            .or_insert_with(|| InstrumentationFile::new(instruction))
+            .instrument(duration, properties);
+    }
+}
+
 #[derive(Debug)]
 struct InstrumentationFile {
     path: PathBuf,
@@ -92,79 +163,92 @@ pub(super) struct ScopedInstrumenter {
 
 impl ScopedInstrumenter {
     pub fn new(instruction: &Instruction) -> Option<Self> {
+        if !filter::allows(instruction) {
+            return None;
+        }
+
+        let instruction_str = instruction_name(instruction);
+
+        let mut properties = Vec::new();
         match instruction {
-            Instruction::F32Load(_)
-            | Instruction::F64Load(_)
-            | Instruction::F32Store(_)
-            | Instruction::F64Store(_)
-            | Instruction::F32Const(_)
-            | Instruction::F64Const(_)
-            | Instruction::F32Eq
-            | Instruction::F32Ne
-            | Instruction::F32Lt
-            | Instruction::F32Gt
-            | Instruction::F32Le
-            | Instruction::F32Ge
-            | Instruction::F64Eq
-            | Instruction::F64Ne
-            | Instruction::F64Lt
-            | Instruction::F64Gt
-            | Instruction::F64Le
-            | Instruction::F64Ge
-            | Instruction::F32Abs
-            | Instruction::F32Neg
-            | Instruction::F32Ceil
-            | Instruction::F32Floor
-            | Instruction::F32Trunc
-            | Instruction::F32Nearest
-            | Instruction::F32Sqrt
-            | Instruction::F32Add
-            | Instruction::F32Sub
-            | Instruction::F32Mul
-            | Instruction::F32Div
-            | Instruction::F32Min
-            | Instruction::F32Max
-            | Instruction::F32Copysign
-            | Instruction::F64Abs
-            | Instruction::F64Neg
-            | Instruction::F64Ceil
-            | Instruction::F64Floor
-            | Instruction::F64Trunc
-            | Instruction::F64Nearest
-            | Instruction::F64Sqrt
-            | Instruction::F64Add
-            | Instruction::F64Sub
-            | Instruction::F64Mul
-            | Instruction::F64Div
-            | Instruction::F64Min
-            | Instruction::F64Max
-            | Instruction::F64Copysign
-            | Instruction::I32TruncSF32
-            | Instruction::I32TruncUF32
-            | Instruction::I32TruncSF64
-            | Instruction::I32TruncUF64
-            | Instruction::I64TruncSF32
-            | Instruction::I64TruncUF32
-            | Instruction::I64TruncSF64
-            | Instruction::I64TruncUF64
-            | Instruction::F32ConvertSI32
-            | Instruction::F32ConvertUI32
-            | Instruction::F32ConvertSI64
-            | Instruction::F32ConvertUI64
-            | Instruction::F32DemoteF64
-            | Instruction::F64ConvertSI32
-            | Instruction::F64ConvertUI32
-            | Instruction::F64ConvertSI64
-            | Instruction::F64ConvertUI64
-            | Instruction::F64PromoteF32
-            | Instruction::I32ReinterpretF32
-            | Instruction::I64ReinterpretF64
-            | Instruction::F32ReinterpretI32
-            | Instruction::F64ReinterpretI64 => return None,
+            Instruction::Br(target)
+            | Instruction::BrIfEqz(target)
+            | Instruction::BrIfNez(target) => {
+                properties.push(target.drop_keep.keep.count().to_string())
+            }
+            Instruction::Return(drop_keep) => properties.push(drop_keep.keep.count().to_string()),
+            Instruction::BrTable(targets) => properties.push(targets.len().to_string()),
+
+            // Memory accesses record the static offset and the natural alignment (the access
+            // width in bytes), so the cost model can separate a small aligned load from a wide one.
+            Instruction::I32Load8S(offset)
+            | Instruction::I32Load8U(offset)
+            | Instruction::I64Load8S(offset)
+            | Instruction::I64Load8U(offset)
+            | Instruction::I32Store8(offset)
+            | Instruction::I64Store8(offset) => push_mem(&mut properties, *offset, 1),
+            Instruction::I32Load16S(offset)
+            | Instruction::I32Load16U(offset)
+            | Instruction::I64Load16S(offset)
+            | Instruction::I64Load16U(offset)
+            | Instruction::I32Store16(offset)
+            | Instruction::I64Store16(offset) => push_mem(&mut properties, *offset, 2),
+            Instruction::I32Load(offset)
+            | Instruction::F32Load(offset)
+            | Instruction::I64Load32S(offset)
+            | Instruction::I64Load32U(offset)
+            | Instruction::I32Store(offset)
+            | Instruction::F32Store(offset)
+            | Instruction::I64Store32(offset) => push_mem(&mut properties, *offset, 4),
+            Instruction::I64Load(offset)
+            | Instruction::F64Load(offset)
+            | Instruction::I64Store(offset)
+            | Instruction::F64Store(offset) => push_mem(&mut properties, *offset, 8),
+
+            // Constants record a magnitude bucket (significant bits) rather than the raw value, so
+            // the column stays bounded while still distinguishing cheap small constants.
+            Instruction::I32Const(value) => {
+                properties.push(magnitude_bucket(u64::from(value.unsigned_abs())).to_string())
+            }
+            Instruction::I64Const(value) => {
+                properties.push(magnitude_bucket(value.unsigned_abs()).to_string())
+            }
+
+            // Calls capture no property: the callee index carries no cost signal, and the real
+            // argument/result arity is not reachable from the instruction alone.
             _ => (),
         };
 
-        let instruction_str = match instruction {
+        Some(ScopedInstrumenter {
+            start: Instant::now(),
+            instruction: instruction_str,
+            properties,
+        })
+    }
+}
+
+/// The directory the CSV sink writes metric files into, and the cost-model pass reads them back.
+pub(super) fn metrics_dir() -> &'static std::path::Path {
+    &OUTPUT_DIR
+}
+
+/// Records a memory access's static `offset` and its natural `alignment` (width in bytes).
+fn push_mem(properties: &mut Vec<String>, offset: u32, alignment: u32) {
+    properties.push(offset.to_string());
+    properties.push(alignment.to_string());
+}
+
+/// Buckets a constant by the number of significant bits in its magnitude (`0` for zero).
+pub(super) fn magnitude_bucket(magnitude: u64) -> u32 {
+    64 - magnitude.leading_zeros()
+}
+
+/// Returns the stable, human-readable name for `instruction`.
+///
+/// Both the timing instrumenter and the [`fuel`] meter key their tables on these names, so they
+/// are enumerated once here rather than duplicated across the two subsystems.
+pub(super) fn instruction_name(instruction: &Instruction) -> &'static str {
+    match instruction {
             Instruction::Unreachable => "Unreachable",
             Instruction::GetLocal(_) => "GetLocal",
             Instruction::SetLocal(_) => "SetLocal",
@@ -332,35 +416,238 @@ impl ScopedInstrumenter {
             Instruction::I64ReinterpretF64 => "I64ReinterpretF64",
             Instruction::F32ReinterpretI32 => "F32ReinterpretI32",
             Instruction::F64ReinterpretI64 => "F64ReinterpretI64",
-        };
+    }
+}
 
-        let mut properties = Vec::new();
-        match instruction {
-            Instruction::Br(target)
-            | Instruction::BrIfEqz(target)
-            | Instruction::BrIfNez(target) => {
-                properties.push(target.drop_keep.keep.count().to_string())
-            }
-            Instruction::Return(drop_keep) => properties.push(drop_keep.keep.count().to_string()),
-            _ => (),
-        };
+/// Every instruction name [`instruction_name`] can produce, used as a fixed interning registry so
+/// the cost-model pass can recover a `'static` key from a name parsed out of a CSV without leaking.
+pub(super) const INSTRUCTION_NAMES: &[&str] = &[
+    "Unreachable",
+    "GetLocal",
+    "SetLocal",
+    "TeeLocal",
+    "Br",
+    "BrIfEqz",
+    "BrIfNez",
+    "BrTable",
+    "Return",
+    "Call",
+    "CallIndirect",
+    "Drop",
+    "Select",
+    "GetGlobal",
+    "SetGlobal",
+    "I32Load",
+    "I64Load",
+    "F32Load",
+    "F64Load",
+    "I32Load8S",
+    "I32Load8U",
+    "I32Load16S",
+    "I32Load16U",
+    "I64Load8S",
+    "I64Load8U",
+    "I64Load16S",
+    "I64Load16U",
+    "I64Load32S",
+    "I64Load32U",
+    "I32Store",
+    "I64Store",
+    "F32Store",
+    "F64Store",
+    "I32Store8",
+    "I32Store16",
+    "I64Store8",
+    "I64Store16",
+    "I64Store32",
+    "CurrentMemory",
+    "GrowMemory",
+    "I32Const",
+    "I64Const",
+    "F32Const",
+    "F64Const",
+    "I32Eqz",
+    "I32Eq",
+    "I32Ne",
+    "I32LtS",
+    "I32LtU",
+    "I32GtS",
+    "I32GtU",
+    "I32LeS",
+    "I32LeU",
+    "I32GeS",
+    "I32GeU",
+    "I64Eqz",
+    "I64Eq",
+    "I64Ne",
+    "I64LtS",
+    "I64LtU",
+    "I64GtS",
+    "I64GtU",
+    "I64LeS",
+    "I64LeU",
+    "I64GeS",
+    "I64GeU",
+    "F32Eq",
+    "F32Ne",
+    "F32Lt",
+    "F32Gt",
+    "F32Le",
+    "F32Ge",
+    "F64Eq",
+    "F64Ne",
+    "F64Lt",
+    "F64Gt",
+    "F64Le",
+    "F64Ge",
+    "I32Clz",
+    "I32Ctz",
+    "I32Popcnt",
+    "I32Add",
+    "I32Sub",
+    "I32Mul",
+    "I32DivS",
+    "I32DivU",
+    "I32RemS",
+    "I32RemU",
+    "I32And",
+    "I32Or",
+    "I32Xor",
+    "I32Shl",
+    "I32ShrS",
+    "I32ShrU",
+    "I32Rotl",
+    "I32Rotr",
+    "I64Clz",
+    "I64Ctz",
+    "I64Popcnt",
+    "I64Add",
+    "I64Sub",
+    "I64Mul",
+    "I64DivS",
+    "I64DivU",
+    "I64RemS",
+    "I64RemU",
+    "I64And",
+    "I64Or",
+    "I64Xor",
+    "I64Shl",
+    "I64ShrS",
+    "I64ShrU",
+    "I64Rotl",
+    "I64Rotr",
+    "F32Abs",
+    "F32Neg",
+    "F32Ceil",
+    "F32Floor",
+    "F32Trunc",
+    "F32Nearest",
+    "F32Sqrt",
+    "F32Add",
+    "F32Sub",
+    "F32Mul",
+    "F32Div",
+    "F32Min",
+    "F32Max",
+    "F32Copysign",
+    "F64Abs",
+    "F64Neg",
+    "F64Ceil",
+    "F64Floor",
+    "F64Trunc",
+    "F64Nearest",
+    "F64Sqrt",
+    "F64Add",
+    "F64Sub",
+    "F64Mul",
+    "F64Div",
+    "F64Min",
+    "F64Max",
+    "F64Copysign",
+    "I32WrapI64",
+    "I32TruncSF32",
+    "I32TruncUF32",
+    "I32TruncSF64",
+    "I32TruncUF64",
+    "I64ExtendSI32",
+    "I64ExtendUI32",
+    "I64TruncSF32",
+    "I64TruncUF32",
+    "I64TruncSF64",
+    "I64TruncUF64",
+    "F32ConvertSI32",
+    "F32ConvertUI32",
+    "F32ConvertSI64",
+    "F32ConvertUI64",
+    "F32DemoteF64",
+    "F64ConvertSI32",
+    "F64ConvertUI32",
+    "F64ConvertSI64",
+    "F64ConvertUI64",
+    "F64PromoteF32",
+    "I32ReinterpretF32",
+    "I64ReinterpretF64",
+    "F32ReinterpretI32",
+    "F64ReinterpretI64",
+];
 
-        Some(ScopedInstrumenter {
-            start: Instant::now(),
-            instruction: instruction_str,
-            properties,
-        })
-    }
+/// Resolves `name` to its canonical `'static` spelling from [`INSTRUCTION_NAMES`], or `None` if it
+/// is not a known instruction.
+pub(super) fn intern(name: &str) -> Option<&'static str> {
+    INSTRUCTION_NAMES
+        .iter()
+        .copied()
+        .find(|known| *known == name)
 }
 
 impl Drop for ScopedInstrumenter {
     fn drop(&mut self) {
+        // Fast path: skip the global lock entirely when no handler is registered, so an
+        // un-instrumented run pays only one atomic load per instruction.
+        if !HANDLER_INSTALLED.load(Ordering::Acquire) {
+            return;
+        }
         let duration = self.start.elapsed();
-        INSTRUMENTATION_FILES
-            .lock()
-            .unwrap()
-            .entry(self.instruction)
-            .or_insert_with(|| InstrumentationFile::new(self.instruction))
-            .instrument(duration, &self.properties);
+        if let Some(handler) = TRACE_HANDLER.lock().unwrap().as_mut() {
+            let keep_going = handler(self.instruction, &self.properties, duration);
+            if !keep_going {
+                halt::request();
+            }
+        }
+    }
+}
+
+/// Tracks whether a [`TraceHandler`] has asked the interpreter to halt.
+///
+/// A handler signals a halt by returning `false`, which sets the flag; [`halt::requested`] reports
+/// and clears it. The interpreter loop does not call [`halt::requested`], so a pending request
+/// does not stop execution.
+pub(super) mod halt {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+    /// Records that the active trace handler asked execution to stop.
+    pub(super) fn request() {
+        REQUESTED.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns and clears any pending halt request raised by the trace handler.
+    pub(crate) fn requested() -> bool {
+        REQUESTED.swap(false, Ordering::SeqCst)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn request_is_observed_once_then_cleared() {
+            assert!(!requested());
+            request();
+            assert!(requested());
+            // The poll clears the flag so a later instruction does not spuriously halt.
+            assert!(!requested());
+        }
     }
 }
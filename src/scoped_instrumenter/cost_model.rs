@@ -0,0 +1,324 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use super::{
+    cost_table::{CostTable, InstructionCost},
+    intern, metrics_dir,
+};
+
+/// Instruction names whose cost scales with the bytes touched rather than a scalar property.
+///
+/// For these the fitter uses the `alignment` (width) column captured in chunk0-4 as its explanatory
+/// variable and maps the fitted slope onto [`InstructionCost::per_byte`]; every other instruction
+/// fits its first property column onto [`InstructionCost::per_property`].
+const MEMORY_INSTRUCTIONS: &[&str] = &[
+    "I32Load", "I64Load", "F32Load", "F64Load", "I32Load8S", "I32Load8U", "I32Load16S",
+    "I32Load16U", "I64Load8S", "I64Load8U", "I64Load16S", "I64Load16U", "I64Load32S", "I64Load32U",
+    "I32Store", "I64Store", "F32Store", "F64Store", "I32Store8", "I32Store16", "I64Store8",
+    "I64Store16", "I64Store32",
+];
+
+/// The linear cost model fitted for a single instruction from its metric corpus.
+///
+/// The model has the form `time = base + slope * feature`, fitted by ordinary least squares over
+/// the `(feature, total_elapsed_time)` pairs recorded in the instruction's CSV. `feature` is the
+/// byte width for memory accesses and the first property column otherwise (e.g. the `keep.count()`
+/// of a branch); instructions that record no feature collapse to a constant `base` with a zero
+/// `slope`. Memory accesses are the exception to the per-CSV fit: their `slope` and `r_squared`
+/// come from one fit pooled across all memory opcodes (see [`fit_metrics_in`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FittedCost {
+    /// The fitted intercept, in seconds.
+    pub base: f64,
+    /// The fitted cost per unit of the feature, in seconds. Zero when there is no feature.
+    pub slope: f64,
+    /// The coefficient of determination (R²) of the fit, in `[0, 1]`; `1.0` for a constant fit.
+    pub r_squared: f64,
+    /// The number of samples the fit was computed from.
+    pub samples: usize,
+}
+
+/// Fits a [`FittedCost`] for every `*.csv` file in the default metrics directory.
+pub fn fit_metrics() -> BTreeMap<String, FittedCost> {
+    fit_metrics_in(metrics_dir())
+}
+
+/// Fits a [`FittedCost`] for every `*.csv` file in `dir`.
+///
+/// Non-memory instructions regress elapsed time against their first property column. Memory
+/// accesses are special: the width column is invariant within a single opcode's CSV (`push_mem`
+/// always writes the same literal width for a given opcode), so a per-file regression on width
+/// degenerates to a constant. Instead the per-byte slope is fitted **once**, pooling rows across
+/// every memory opcode where width genuinely varies (see [`pooled_memory_fit`]); each opcode then
+/// keeps its own intercept so the shared slope explains the cost difference between widths.
+pub fn fit_metrics_in(dir: &Path) -> BTreeMap<String, FittedCost> {
+    let corpus = read_corpus(dir);
+    let memory_fit = pooled_memory_fit(&corpus);
+    let mut fits = BTreeMap::new();
+    for (name, rows) in &corpus {
+        let fit = if is_memory(name) {
+            match (memory_fit, width_of(rows)) {
+                (Some(shared), Some(width)) => FittedCost {
+                    base: mean_time(rows) - shared.slope * width,
+                    slope: shared.slope,
+                    r_squared: shared.r_squared,
+                    samples: rows.len(),
+                },
+                _ => continue,
+            }
+        } else {
+            let samples: Vec<(f64, f64)> = rows
+                .iter()
+                .map(|(columns, time)| (columns.first().copied().unwrap_or(0.0), *time))
+                .collect();
+            match fit_samples(&samples) {
+                Some(fit) => fit,
+                None => continue,
+            }
+        };
+        fits.insert(name.clone(), fit);
+    }
+    fits
+}
+
+/// Fits the shared per-byte slope by pooling `(width, elapsed_time)` rows across all memory
+/// opcodes, where the width varies even though it is constant within any single opcode's file.
+fn pooled_memory_fit(corpus: &BTreeMap<String, Vec<(Vec<f64>, f64)>>) -> Option<FittedCost> {
+    let samples: Vec<(f64, f64)> = corpus
+        .iter()
+        .filter(|(name, _)| is_memory(name))
+        .flat_map(|(_, rows)| rows.iter())
+        .filter_map(|(columns, time)| columns.get(1).map(|&width| (width, *time)))
+        .collect();
+    fit_samples(&samples)
+}
+
+/// The (file-constant) byte width recorded for a memory opcode's rows.
+fn width_of(rows: &[(Vec<f64>, f64)]) -> Option<f64> {
+    rows.first().and_then(|(columns, _)| columns.get(1).copied())
+}
+
+/// The mean elapsed time across an opcode's rows.
+fn mean_time(rows: &[(Vec<f64>, f64)]) -> f64 {
+    if rows.is_empty() {
+        return 0.0;
+    }
+    rows.iter().map(|(_, time)| *time).sum::<f64>() / rows.len() as f64
+}
+
+/// Fits the metrics and converts them into a [`CostTable`] ready for the fuel meter.
+///
+/// `gas_per_second` scales the fitted wall-clock coefficients into integer gas units; pick it so
+/// the cheapest instruction rounds to a small positive cost. A base that rounds below one is
+/// clamped up to one so no instruction is ever free, a negative slope is clamped to zero, and the
+/// fitted slope is mapped onto `per_byte`
+/// for memory accesses and `per_property` otherwise, matching how the fuel meter prices them.
+pub fn fit_cost_table(gas_per_second: f64) -> CostTable {
+    let mut table = CostTable::uniform(InstructionCost::flat(1));
+    for (name, fit) in fit_metrics() {
+        let key = match intern(&name) {
+            Some(key) => key,
+            None => continue, // ignore stray files that do not name a known instruction
+        };
+        let base = ((fit.base * gas_per_second).round() as i64).max(1) as u64;
+        let slope = ((fit.slope * gas_per_second).round() as i64).max(0) as u64;
+        let cost = if is_memory(key) {
+            InstructionCost {
+                base,
+                per_property: 0,
+                per_byte: slope,
+            }
+        } else {
+            InstructionCost {
+                base,
+                per_property: slope,
+                per_byte: 0,
+            }
+        };
+        table.set(key, cost);
+    }
+    table
+}
+
+/// Reads every `*.csv` in `dir`, returning parsed `(columns, time)` rows keyed by instruction name.
+fn read_corpus(dir: &Path) -> BTreeMap<String, Vec<(Vec<f64>, f64)>> {
+    let mut corpus = BTreeMap::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return corpus,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("csv") {
+            continue;
+        }
+        let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        if let Ok(contents) = fs::read_to_string(&path) {
+            corpus.insert(name, parse_rows(&contents));
+        }
+    }
+    corpus
+}
+
+fn is_memory(name: &str) -> bool {
+    MEMORY_INSTRUCTIONS.contains(&name)
+}
+
+/// Parses the `(columns, total_elapsed_time)` rows out of one CSV file's contents.
+fn parse_rows(contents: &str) -> Vec<(Vec<f64>, f64)> {
+    contents
+        .lines()
+        .skip(1) // discount the header line
+        .filter_map(parse_row)
+        .collect()
+}
+
+/// Parses a single `"(args,)",n_exec,total_elapsed_time` row into its numeric columns and time.
+fn parse_row(row: &str) -> Option<(Vec<f64>, f64)> {
+    // The args column is a quoted tuple; everything after the closing quote is `,n_exec,time`.
+    let end = row.rfind('"')?;
+    let args = row.get(1..end)?;
+    let tail = row.get(end + 1..)?;
+    let time: f64 = tail.rsplit(',').next()?.trim().parse().ok()?;
+
+    let columns = args
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .filter_map(|value| value.parse::<f64>().ok())
+        .collect();
+
+    Some((columns, time))
+}
+
+/// Fits `time = base + slope * feature` by ordinary least squares.
+///
+/// Returns `None` for an empty corpus. When the feature has no variance (every sample shares the
+/// same value, including the feature-less case), the slope is zero and the fit is the sample mean
+/// with an R² of `1.0`.
+fn fit_samples(samples: &[(f64, f64)]) -> Option<FittedCost> {
+    let n = samples.len();
+    if n == 0 {
+        return None;
+    }
+    let count = n as f64;
+    let mean_x = samples.iter().map(|&(x, _)| x).sum::<f64>() / count;
+    let mean_y = samples.iter().map(|&(_, y)| y).sum::<f64>() / count;
+
+    let mut sxx = 0.0;
+    let mut sxy = 0.0;
+    let mut syy = 0.0;
+    for &(x, y) in samples {
+        sxx += (x - mean_x) * (x - mean_x);
+        sxy += (x - mean_x) * (y - mean_y);
+        syy += (y - mean_y) * (y - mean_y);
+    }
+
+    if sxx == 0.0 {
+        // No variance in the feature: fall back to a constant model at the mean.
+        return Some(FittedCost {
+            base: mean_y,
+            slope: 0.0,
+            r_squared: 1.0,
+            samples: n,
+        });
+    }
+
+    let slope = sxy / sxx;
+    let base = mean_y - slope * mean_x;
+    let r_squared = if syy == 0.0 {
+        1.0
+    } else {
+        (sxy * sxy) / (sxx * syy)
+    };
+
+    Some(FittedCost {
+        base,
+        slope,
+        r_squared,
+        samples: n,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_row_extracts_columns_and_time() {
+        let (columns, time) = parse_row("\"(12,4,)\",1,2.500000e-06").unwrap();
+        assert_eq!(columns, vec![12.0, 4.0]);
+        assert!((time - 2.5e-6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn parse_row_handles_empty_property_tuple() {
+        let (columns, time) = parse_row("\"()\",1,1.000000e-06").unwrap();
+        assert!(columns.is_empty());
+        assert!((time - 1.0e-6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn parse_row_rejects_malformed_rows() {
+        assert!(parse_row("not a row").is_none());
+    }
+
+    #[test]
+    fn fit_constant_when_feature_has_no_variance() {
+        let fit = fit_samples(&[(0.0, 2.0), (0.0, 4.0)]).unwrap();
+        assert_eq!(fit.slope, 0.0);
+        assert!((fit.base - 3.0).abs() < 1e-12);
+        assert_eq!(fit.r_squared, 1.0);
+    }
+
+    #[test]
+    fn fit_recovers_a_known_line() {
+        // time = 1 + 2 * feature
+        let fit = fit_samples(&[(0.0, 1.0), (1.0, 3.0), (2.0, 5.0), (3.0, 7.0)]).unwrap();
+        assert!((fit.base - 1.0).abs() < 1e-9);
+        assert!((fit.slope - 2.0).abs() < 1e-9);
+        assert!((fit.r_squared - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fit_allows_negative_base() {
+        // A line that crosses below zero at feature = 0 must keep its negative intercept.
+        let fit = fit_samples(&[(2.0, 1.0), (4.0, 3.0)]).unwrap();
+        assert!((fit.slope - 1.0).abs() < 1e-9);
+        assert!((fit.base + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn memory_classification() {
+        assert!(is_memory("F64Load"));
+        assert!(is_memory("I64Store"));
+        assert!(!is_memory("I32Add"));
+        assert!(!is_memory("Br"));
+    }
+
+    #[test]
+    fn pooled_memory_fit_recovers_a_per_byte_slope() {
+        // Two opcodes, each with a file-constant width; width varies across opcodes so the pooled
+        // regression can recover the per-byte slope that a per-file fit cannot.
+        let mut corpus = BTreeMap::new();
+        // time = 10 + 2 * width
+        corpus.insert(
+            "I32Load".to_owned(),
+            vec![(vec![0.0, 4.0], 18.0), (vec![8.0, 4.0], 18.0)],
+        );
+        corpus.insert(
+            "I64Load".to_owned(),
+            vec![(vec![0.0, 8.0], 26.0), (vec![8.0, 8.0], 26.0)],
+        );
+        let fit = pooled_memory_fit(&corpus).unwrap();
+        assert!((fit.slope - 2.0).abs() < 1e-9);
+
+        // Each opcode keeps its own intercept under the shared slope.
+        let metrics_base = mean_time(&corpus["I64Load"]) - fit.slope * 8.0;
+        assert!((metrics_base - 10.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+/// The fuel cost of a single instruction, expressed as a small linear model.
+///
+/// The charge for one execution is `base + per_property * property + per_byte * bytes`, where
+/// `property` is an instruction-specific feature (the `keep.count()` of a branch or return, for
+/// example) and `bytes` is the width touched by a memory access. Instructions that expose no
+/// property or touch no memory simply leave the corresponding coefficient at zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstructionCost {
+    /// The fixed cost charged for every execution of the instruction.
+    pub base: u64,
+    /// Additional cost charged per unit of the captured property (e.g. per kept value).
+    pub per_property: u64,
+    /// Additional cost charged per byte moved by a memory access.
+    pub per_byte: u64,
+}
+
+impl InstructionCost {
+    /// A cost of `base` with no property- or byte-dependent component.
+    pub const fn flat(base: u64) -> Self {
+        InstructionCost {
+            base,
+            per_property: 0,
+            per_byte: 0,
+        }
+    }
+
+    /// Evaluates the linear model for the given property count and byte width.
+    pub fn evaluate(&self, property: u64, bytes: u64) -> u64 {
+        self.base
+            .saturating_add(self.per_property.saturating_mul(property))
+            .saturating_add(self.per_byte.saturating_mul(bytes))
+    }
+}
+
+/// A deterministic, platform-independent gas schedule keyed by instruction name.
+///
+/// Instruction names are the ones produced by [`instruction_name`](super::instruction_name), so a
+/// table populated here lines up exactly with the names [`FuelMeter`](super::FuelMeter) charges against.
+/// Entries absent from the table fall back to [`CostTable::default_cost`].
+#[derive(Debug, Clone)]
+pub struct CostTable {
+    costs: HashMap<&'static str, InstructionCost>,
+    default_cost: InstructionCost,
+}
+
+impl CostTable {
+    /// Creates an empty table in which every instruction resolves to `default_cost`.
+    pub fn uniform(default_cost: InstructionCost) -> Self {
+        CostTable {
+            costs: HashMap::new(),
+            default_cost,
+        }
+    }
+
+    /// Sets the cost model for a single instruction, returning the table for chaining.
+    pub fn with(mut self, instruction: &'static str, cost: InstructionCost) -> Self {
+        self.costs.insert(instruction, cost);
+        self
+    }
+
+    /// Sets the cost model for a single instruction in place.
+    pub fn set(&mut self, instruction: &'static str, cost: InstructionCost) {
+        self.costs.insert(instruction, cost);
+    }
+
+    /// The cost model applied to instructions without an explicit entry.
+    pub fn default_cost(&self) -> InstructionCost {
+        self.default_cost
+    }
+
+    /// Looks up the cost model for `instruction`, falling back to the default.
+    pub fn cost_of(&self, instruction: &str) -> InstructionCost {
+        self.costs
+            .get(instruction)
+            .copied()
+            .unwrap_or(self.default_cost)
+    }
+}
+
+impl Default for CostTable {
+    /// A table charging one unit per instruction, with branches and returns scaling by the number
+    /// of kept values (`BrTable` by its number of targets) and memory accesses adding a per-byte
+    /// component.
+    fn default() -> Self {
+        let branch = InstructionCost {
+            base: 1,
+            per_property: 1,
+            per_byte: 0,
+        };
+        let memory = InstructionCost {
+            base: 1,
+            per_property: 0,
+            per_byte: 1,
+        };
+        CostTable::uniform(InstructionCost::flat(1))
+            .with("Br", branch)
+            .with("BrIfEqz", branch)
+            .with("BrIfNez", branch)
+            .with("BrTable", branch)
+            .with("Return", branch)
+            .with("I32Load", memory)
+            .with("I64Load", memory)
+            .with("F32Load", memory)
+            .with("F64Load", memory)
+            .with("I32Load8S", memory)
+            .with("I32Load8U", memory)
+            .with("I32Load16S", memory)
+            .with("I32Load16U", memory)
+            .with("I64Load8S", memory)
+            .with("I64Load8U", memory)
+            .with("I64Load16S", memory)
+            .with("I64Load16U", memory)
+            .with("I64Load32S", memory)
+            .with("I64Load32U", memory)
+            .with("I32Store", memory)
+            .with("I64Store", memory)
+            .with("F32Store", memory)
+            .with("F64Store", memory)
+            .with("I32Store8", memory)
+            .with("I32Store16", memory)
+            .with("I64Store8", memory)
+            .with("I64Store16", memory)
+            .with("I64Store32", memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate_combines_base_property_and_bytes() {
+        let cost = InstructionCost {
+            base: 2,
+            per_property: 3,
+            per_byte: 5,
+        };
+        assert_eq!(cost.evaluate(4, 8), 2 + 3 * 4 + 5 * 8);
+    }
+
+    #[test]
+    fn evaluate_saturates_instead_of_overflowing() {
+        let cost = InstructionCost {
+            base: 1,
+            per_property: u64::MAX,
+            per_byte: 0,
+        };
+        assert_eq!(cost.evaluate(u64::MAX, 0), u64::MAX);
+    }
+
+    #[test]
+    fn cost_of_falls_back_to_default() {
+        let table = CostTable::uniform(InstructionCost::flat(9)).with("Br", InstructionCost::flat(1));
+        assert_eq!(table.cost_of("Br"), InstructionCost::flat(1));
+        assert_eq!(table.cost_of("I32Add"), InstructionCost::flat(9));
+    }
+
+    #[test]
+    fn default_table_prices_branches_and_memory() {
+        let table = CostTable::default();
+        // Branch cost scales with the kept-value count.
+        assert_eq!(table.cost_of("Br").evaluate(3, 0), 1 + 3);
+        // Memory cost scales with the byte width.
+        assert_eq!(table.cost_of("I64Store").evaluate(0, 8), 1 + 8);
+    }
+}
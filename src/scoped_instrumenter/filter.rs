@@ -0,0 +1,265 @@
+use std::{collections::HashSet, env, sync::Mutex};
+
+use lazy_static::lazy_static;
+
+use crate::isa::Instruction;
+
+use super::instruction_name;
+
+/// The environment variable consulted for the default instrumentation filter.
+///
+/// It holds a comma-separated list of tokens. Group keywords (`int`/`integer`, `float`/`f32`/`f64`,
+/// `all`, matched case-insensitively)
+/// select whole instruction families; any other token is treated as an instruction name and
+/// narrows instrumentation to exactly the named opcodes. For example `WASMI_INSTRUMENT=int,float`
+/// instruments everything, while `WASMI_INSTRUMENT=I32Add,Br` instruments just those two.
+pub const FILTER_ENV: &str = "WASMI_INSTRUMENT";
+
+lazy_static! {
+    static ref FILTER: Mutex<InstructionFilter> = Mutex::new(InstructionFilter::from_env());
+}
+
+/// A coarse family of instructions that can be instrumented as a unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InstructionGroup {
+    /// Integer, control-flow, memory and parametric instructions — the historical default set.
+    Integer,
+    /// The `F32*`/`F64*` and float-conversion family, excluded by default.
+    Float,
+}
+
+/// Selects which instructions the timing instrumenter records.
+///
+/// By default only the [`Integer`](InstructionGroup::Integer) group is instrumented, reproducing
+/// the previous compile-time exclusion of floats while letting embedders who do allow floats opt
+/// the [`Float`](InstructionGroup::Float) group back in. A filter can also be narrowed to a
+/// specific set of opcode names to keep overhead low when chasing a single hotspot.
+#[derive(Debug, Clone)]
+pub struct InstructionFilter {
+    groups: HashSet<InstructionGroup>,
+    only: Option<HashSet<String>>,
+}
+
+impl InstructionFilter {
+    /// A filter instrumenting only the integer group — the default behaviour.
+    pub fn integer_only() -> Self {
+        let mut groups = HashSet::new();
+        groups.insert(InstructionGroup::Integer);
+        InstructionFilter { groups, only: None }
+    }
+
+    /// A filter instrumenting every instruction group.
+    pub fn all() -> Self {
+        let mut filter = InstructionFilter::integer_only();
+        filter.groups.insert(InstructionGroup::Float);
+        filter
+    }
+
+    /// Adds `group` to the set of instrumented families, returning the filter for chaining.
+    pub fn with_group(mut self, group: InstructionGroup) -> Self {
+        self.groups.insert(group);
+        self
+    }
+
+    /// Narrows the filter to exactly the named opcodes, regardless of group.
+    pub fn only<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.only = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Parses a filter from [`FILTER_ENV`], falling back to [`integer_only`](Self::integer_only).
+    pub fn from_env() -> Self {
+        match env::var(FILTER_ENV) {
+            Ok(value) => Self::parse(&value),
+            Err(_) => Self::integer_only(),
+        }
+    }
+
+    /// Parses a comma-separated filter specification.
+    fn parse(spec: &str) -> Self {
+        let mut groups = HashSet::new();
+        let mut only = HashSet::new();
+        for token in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            match token.to_ascii_lowercase().as_str() {
+                "all" => {
+                    groups.insert(InstructionGroup::Integer);
+                    groups.insert(InstructionGroup::Float);
+                }
+                "int" | "integer" => {
+                    groups.insert(InstructionGroup::Integer);
+                }
+                "float" | "f32" | "f64" => {
+                    groups.insert(InstructionGroup::Float);
+                }
+                _ => {
+                    only.insert(token.to_owned());
+                }
+            }
+        }
+        if only.is_empty() {
+            if groups.is_empty() {
+                groups.insert(InstructionGroup::Integer);
+            }
+            InstructionFilter { groups, only: None }
+        } else {
+            // An explicit opcode list narrows instrumentation and overrides group selection.
+            InstructionFilter {
+                groups,
+                only: Some(only),
+            }
+        }
+    }
+
+    /// Returns whether `instruction` should be instrumented under this filter.
+    pub fn allows(&self, instruction: &Instruction) -> bool {
+        if let Some(only) = &self.only {
+            return only.contains(instruction_name(instruction));
+        }
+        self.groups.contains(&group_of(instruction))
+    }
+}
+
+impl Default for InstructionFilter {
+    fn default() -> Self {
+        InstructionFilter::integer_only()
+    }
+}
+
+/// Installs `filter` as the global instrumentation filter, replacing any previous one.
+pub fn set_instruction_filter(filter: InstructionFilter) {
+    *FILTER.lock().unwrap() = filter;
+}
+
+/// Returns whether the global filter currently instruments `instruction`.
+pub(super) fn allows(instruction: &Instruction) -> bool {
+    FILTER.lock().unwrap().allows(instruction)
+}
+
+/// Classifies `instruction` into its [`InstructionGroup`].
+fn group_of(instruction: &Instruction) -> InstructionGroup {
+    match instruction {
+        Instruction::F32Load(_)
+        | Instruction::F64Load(_)
+        | Instruction::F32Store(_)
+        | Instruction::F64Store(_)
+        | Instruction::F32Const(_)
+        | Instruction::F64Const(_)
+        | Instruction::F32Eq
+        | Instruction::F32Ne
+        | Instruction::F32Lt
+        | Instruction::F32Gt
+        | Instruction::F32Le
+        | Instruction::F32Ge
+        | Instruction::F64Eq
+        | Instruction::F64Ne
+        | Instruction::F64Lt
+        | Instruction::F64Gt
+        | Instruction::F64Le
+        | Instruction::F64Ge
+        | Instruction::F32Abs
+        | Instruction::F32Neg
+        | Instruction::F32Ceil
+        | Instruction::F32Floor
+        | Instruction::F32Trunc
+        | Instruction::F32Nearest
+        | Instruction::F32Sqrt
+        | Instruction::F32Add
+        | Instruction::F32Sub
+        | Instruction::F32Mul
+        | Instruction::F32Div
+        | Instruction::F32Min
+        | Instruction::F32Max
+        | Instruction::F32Copysign
+        | Instruction::F64Abs
+        | Instruction::F64Neg
+        | Instruction::F64Ceil
+        | Instruction::F64Floor
+        | Instruction::F64Trunc
+        | Instruction::F64Nearest
+        | Instruction::F64Sqrt
+        | Instruction::F64Add
+        | Instruction::F64Sub
+        | Instruction::F64Mul
+        | Instruction::F64Div
+        | Instruction::F64Min
+        | Instruction::F64Max
+        | Instruction::F64Copysign
+        | Instruction::I32TruncSF32
+        | Instruction::I32TruncUF32
+        | Instruction::I32TruncSF64
+        | Instruction::I32TruncUF64
+        | Instruction::I64TruncSF32
+        | Instruction::I64TruncUF32
+        | Instruction::I64TruncSF64
+        | Instruction::I64TruncUF64
+        | Instruction::F32ConvertSI32
+        | Instruction::F32ConvertUI32
+        | Instruction::F32ConvertSI64
+        | Instruction::F32ConvertUI64
+        | Instruction::F32DemoteF64
+        | Instruction::F64ConvertSI32
+        | Instruction::F64ConvertUI32
+        | Instruction::F64ConvertSI64
+        | Instruction::F64ConvertUI64
+        | Instruction::F64PromoteF32
+        | Instruction::I32ReinterpretF32
+        | Instruction::I64ReinterpretF64
+        | Instruction::F32ReinterpretI32
+        | Instruction::F64ReinterpretI64 => InstructionGroup::Float,
+        _ => InstructionGroup::Integer,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups(filter: &InstructionFilter) -> &HashSet<InstructionGroup> {
+        &filter.groups
+    }
+
+    #[test]
+    fn empty_spec_defaults_to_integer_only() {
+        let filter = InstructionFilter::parse("");
+        assert!(groups(&filter).contains(&InstructionGroup::Integer));
+        assert!(!groups(&filter).contains(&InstructionGroup::Float));
+        assert!(filter.only.is_none());
+    }
+
+    #[test]
+    fn group_keywords_select_families() {
+        let filter = InstructionFilter::parse("int, float");
+        assert!(groups(&filter).contains(&InstructionGroup::Integer));
+        assert!(groups(&filter).contains(&InstructionGroup::Float));
+        assert!(filter.only.is_none());
+
+        let all = InstructionFilter::parse("all");
+        assert!(groups(&all).contains(&InstructionGroup::Integer));
+        assert!(groups(&all).contains(&InstructionGroup::Float));
+    }
+
+    #[test]
+    fn opcode_names_narrow_and_override_groups() {
+        let filter = InstructionFilter::parse("I32Add, Br");
+        let only = filter.only.as_ref().expect("opcode list narrows the filter");
+        assert!(only.contains("I32Add"));
+        assert!(only.contains("Br"));
+        assert_eq!(only.len(), 2);
+
+        // A group keyword alongside opcode names does not widen the filter to the whole group.
+        let mixed = InstructionFilter::parse("int, I32Add");
+        assert!(mixed.allows(&Instruction::I32Add));
+        assert!(!mixed.allows(&Instruction::I64Add));
+    }
+
+    #[test]
+    fn float_alone_leaves_integer_off() {
+        let filter = InstructionFilter::parse("float");
+        assert!(groups(&filter).contains(&InstructionGroup::Float));
+        assert!(!groups(&filter).contains(&InstructionGroup::Integer));
+    }
+}
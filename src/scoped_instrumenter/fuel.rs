@@ -0,0 +1,188 @@
+use std::fmt;
+
+use crate::isa::Instruction;
+
+use super::{cost_table::CostTable, instruction_name, magnitude_bucket};
+
+/// The error raised when execution would exceed its fuel budget.
+///
+/// It is returned by [`FuelMeter::charge`]; no conversion from it into an interpreter trap exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuelError {
+    /// The remaining budget was smaller than the cost of the next instruction.
+    OutOfFuel,
+}
+
+impl fmt::Display for FuelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FuelError::OutOfFuel => write!(f, "out of fuel"),
+        }
+    }
+}
+
+/// Deterministic fuel accounting for a single execution.
+///
+/// Unlike [`ScopedInstrumenter`](super::ScopedInstrumenter), which measures non-deterministic
+/// wall-clock durations, the meter charges a fixed, table-driven cost for each instruction and is
+/// therefore reproducible across platforms — the property Casper needs for consensus-critical gas.
+///
+/// A meter is a plain value rather than a process global, so separate meters keep independent
+/// budgets and [`remaining`](Self::remaining) is deterministic per instance. The interpreter does
+/// not hold a meter or call [`charge`](Self::charge): fuel is deducted only where the owner of a
+/// meter charges instructions itself, and [`FuelError::OutOfFuel`] is returned to that caller.
+#[derive(Debug, Clone)]
+pub struct FuelMeter {
+    remaining: u64,
+    table: CostTable,
+}
+
+impl Default for FuelMeter {
+    fn default() -> Self {
+        FuelMeter {
+            remaining: u64::MAX,
+            table: CostTable::default(),
+        }
+    }
+}
+
+impl FuelMeter {
+    /// Creates a meter with `budget` fuel and the default cost schedule.
+    pub fn new(budget: u64) -> Self {
+        FuelMeter {
+            remaining: budget,
+            table: CostTable::default(),
+        }
+    }
+
+    /// Creates a meter with `budget` fuel priced by `table`.
+    pub fn with_cost_table(budget: u64, table: CostTable) -> Self {
+        FuelMeter {
+            remaining: budget,
+            table,
+        }
+    }
+
+    /// Sets the remaining fuel budget.
+    pub fn set_fuel(&mut self, budget: u64) {
+        self.remaining = budget;
+    }
+
+    /// Returns the fuel left after the instructions charged so far.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Replaces the cost schedule used to price instructions.
+    pub fn set_cost_table(&mut self, table: CostTable) {
+        self.table = table;
+    }
+
+    /// Deducts the cost of `instruction` from the budget.
+    ///
+    /// Returns [`FuelError::OutOfFuel`] when the budget would go negative; in that case the
+    /// remaining fuel is pinned to zero and the caller must not execute the instruction.
+    pub fn charge(&mut self, instruction: &Instruction) -> Result<(), FuelError> {
+        let cost = self
+            .table
+            .cost_of(instruction_name(instruction))
+            .evaluate(property_of(instruction), bytes_of(instruction));
+        self.charge_cost(cost)
+    }
+
+    /// Subtracts a pre-computed `cost` from the budget, trapping on underflow.
+    fn charge_cost(&mut self, cost: u64) -> Result<(), FuelError> {
+        match self.remaining.checked_sub(cost) {
+            Some(remaining) => {
+                self.remaining = remaining;
+                Ok(())
+            }
+            None => {
+                self.remaining = 0;
+                Err(FuelError::OutOfFuel)
+            }
+        }
+    }
+}
+
+/// The feature value fed into an instruction's `per_property` term.
+///
+/// This mirrors the primary feature captured by [`ScopedInstrumenter`](super::ScopedInstrumenter)
+/// and fitted by the cost model, so the slope the model learns is multiplied by the same quantity
+/// at charge time: branch/return keep counts, `br_table` target counts, and constant magnitude
+/// buckets. Calls report no property (the callee index is not a cost signal), and memory accesses
+/// price through the byte-width term instead (see [`bytes_of`]).
+fn property_of(instruction: &Instruction) -> u64 {
+    match instruction {
+        Instruction::Br(target)
+        | Instruction::BrIfEqz(target)
+        | Instruction::BrIfNez(target) => u64::from(target.drop_keep.keep.count()),
+        Instruction::Return(drop_keep) => u64::from(drop_keep.keep.count()),
+        Instruction::BrTable(targets) => targets.len() as u64,
+        Instruction::I32Const(value) => u64::from(magnitude_bucket(u64::from(value.unsigned_abs()))),
+        Instruction::I64Const(value) => u64::from(magnitude_bucket(value.unsigned_abs())),
+        _ => 0,
+    }
+}
+
+/// The number of bytes moved by a memory access, fed into the `per_byte` term; zero otherwise.
+fn bytes_of(instruction: &Instruction) -> u64 {
+    match instruction {
+        Instruction::I32Load8S(_)
+        | Instruction::I32Load8U(_)
+        | Instruction::I64Load8S(_)
+        | Instruction::I64Load8U(_)
+        | Instruction::I32Store8(_)
+        | Instruction::I64Store8(_) => 1,
+        Instruction::I32Load16S(_)
+        | Instruction::I32Load16U(_)
+        | Instruction::I64Load16S(_)
+        | Instruction::I64Load16U(_)
+        | Instruction::I32Store16(_)
+        | Instruction::I64Store16(_) => 2,
+        Instruction::I32Load(_)
+        | Instruction::F32Load(_)
+        | Instruction::I64Load32S(_)
+        | Instruction::I64Load32U(_)
+        | Instruction::I32Store(_)
+        | Instruction::F32Store(_)
+        | Instruction::I64Store32(_) => 4,
+        Instruction::I64Load(_)
+        | Instruction::F64Load(_)
+        | Instruction::I64Store(_)
+        | Instruction::F64Store(_) => 8,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::cost_table::InstructionCost;
+
+    #[test]
+    fn charge_cost_subtracts_until_exhausted() {
+        let mut meter = FuelMeter::new(10);
+        assert_eq!(meter.charge_cost(4), Ok(()));
+        assert_eq!(meter.remaining(), 6);
+        assert_eq!(meter.charge_cost(6), Ok(()));
+        assert_eq!(meter.remaining(), 0);
+    }
+
+    #[test]
+    fn charge_cost_traps_and_pins_to_zero_on_underflow() {
+        let mut meter = FuelMeter::new(3);
+        assert_eq!(meter.charge_cost(5), Err(FuelError::OutOfFuel));
+        assert_eq!(meter.remaining(), 0);
+    }
+
+    #[test]
+    fn set_fuel_and_cost_table_take_effect() {
+        let mut meter = FuelMeter::new(0);
+        meter.set_fuel(100);
+        meter.set_cost_table(CostTable::uniform(InstructionCost::flat(7)));
+        assert_eq!(meter.remaining(), 100);
+        assert_eq!(meter.charge(&Instruction::I32Add), Ok(()));
+        assert_eq!(meter.remaining(), 93);
+    }
+}